@@ -21,7 +21,7 @@ fn main() {
         channel_sample_count: 512,
     };
 
-    let (engine_main, engine_proc) = engine::new_engine(44100.0, 512);
+    let (engine_main, engine_proc, engine_events) = engine::new_engine(44100.0, 512);
     let _device = run_output_device(params, {
         move |data| {
             for samples in data.chunks_mut(params.channels_count) {
@@ -39,36 +39,36 @@ fn main() {
         .enable_all()
         .build()
         .unwrap()
-        .block_on(run_event_loop_main(addr, engine_main))
+        .block_on(run_event_loop_main(addr, engine_main, engine_events))
         .expect("Failed to start event loop")
 }
 
-async fn run_event_loop_main(addr: String, engine_main: engine::MainHandle) -> Result<(), Error> {
+async fn run_event_loop_main(
+    addr: String,
+    engine_main: engine::MainHandle,
+    engine_events: engine::EventStream,
+) -> Result<(), Error> {
+    // This mutex only guards `MainHandle`'s own bookkeeping (`node_map`, the
+    // command queue handle) between the poller and connection tasks below --
+    // `MainHandle::render` hands work to the audio thread over a lock-free
+    // queue, so this lock is never held across the audio path and never
+    // contends with `ProcessHandle::process`.
     let shared_engine_main = Arc::new(Mutex::new(engine_main));
 
     let (first, second) = tokio::join!(
-        tokio::spawn(run_event_poller(shared_engine_main.clone())),
+        tokio::spawn(run_event_poller(engine_events)),
         tokio::spawn(run_tcp_listener(addr, shared_engine_main.clone())),
     );
 
     first.unwrap_or(second.unwrap_or(Ok(())))
 }
 
-async fn run_event_poller(engine_main: Arc<Mutex<engine::MainHandle>>) -> Result<(), Error> {
-    let mut interval =
-        tokio::time::interval(tokio::time::Duration::from_millis((1000.0 / 30.0) as u64));
-
-    loop {
-        interval.tick().await;
-
-        if let Ok(result) = engine_main.lock().unwrap().process_queued_events() {
-            if let Some(events) = result.as_array() {
-                for evt in events.iter() {
-                    println!("[Event] {}", evt.to_string());
-                }
-            }
-        }
+async fn run_event_poller(mut engine_events: engine::EventStream) -> Result<(), Error> {
+    while let Some(event) = engine_events.next().await {
+        println!("[Event] {:?}", event);
     }
+
+    Ok(())
 }
 
 async fn run_tcp_listener(
@@ -103,23 +103,53 @@ async fn accept_connection(stream: TcpStream, engine_main: Arc<Mutex<engine::Mai
 
     while let Ok(next) = read.try_next().await {
         if let Some(msg) = next {
-            match msg.to_text() {
-                Ok(text) => {
-                    println!("Received a message from {}: {}", addr, text);
-                    let directive: server::UnresolvedDirective =
-                        serde_json::from_str(text).unwrap_or_default();
-                    let resolved = server::resolve_directive(directive).await;
-
-                    {
-                        let mut main = engine_main.lock().unwrap();
-                        let _ = main.render(resolved);
+            let format = if msg.is_binary() {
+                server::wire::WireFormat::Preserves
+            } else {
+                server::wire::WireFormat::Json
+            };
+
+            match server::wire::decode_directive(format, &msg.clone().into_data()) {
+                Ok(directive) => {
+                    println!("Received a {:?} directive from {}", format, addr);
+
+                    match server::resolve_directive(directive).await {
+                        Ok(resolved) => {
+                            let rendered = {
+                                let mut main = engine_main.lock().unwrap();
+                                main.render(resolved)
+                            };
+
+                            // TODO: Properly handle the write failure case
+                            match rendered {
+                                Ok(Some(instructions)) => {
+                                    let bytes = server::wire::encode_instructions(format, &instructions);
+                                    let reply = match format {
+                                        server::wire::WireFormat::Json => {
+                                            tokio_tungstenite::tungstenite::Message::Text(
+                                                String::from_utf8_lossy(&bytes).into_owned(),
+                                            )
+                                        }
+                                        server::wire::WireFormat::Preserves => {
+                                            tokio_tungstenite::tungstenite::Message::Binary(bytes)
+                                        }
+                                    };
+                                    write.send(reply).await.unwrap()
+                                }
+                                // No graph in the directive (a resources-only
+                                // update): nothing was reconciled, so just ack.
+                                Ok(None) => write.send(msg).await.unwrap(),
+                                Err(e) => write.send(format!("Error: {e}").into()).await.unwrap(),
+                            }
+                        }
+                        Err(e) => {
+                            println!("Failed to resolve resources for {}: {}", addr, e);
+                            write.send(format!("Error: {e}").into()).await.unwrap()
+                        }
                     }
-
-                    // TODO: Properly handle the write failure case
-                    write.send(msg).await.unwrap()
                 }
                 Err(e) => {
-                    println!("Received a non-text message from {}: {}", addr, e);
+                    println!("Failed to decode message from {}: {}", addr, e);
                     write.send("No thanks".into()).await.unwrap()
                 }
             }
@@ -10,7 +10,7 @@ pub struct EngineHandles {
 
 #[no_mangle]
 pub extern "C" fn elem_engine_new(sample_rate: f64, block_size: usize) -> *mut EngineHandles {
-    let (mut main, proc) = elem::engine::new_engine(sample_rate, block_size);
+    let (mut main, proc, _events) = elem::engine::new_engine(sample_rate, block_size);
 
     // So assuming that I have a static audio process that I want to run elsewhere, all I have to
     // do here is build it so that the engine state is as I want before we return over the ffi.
@@ -22,7 +22,7 @@ pub extern "C" fn elem_engine_new(sample_rate: f64, block_size: usize) -> *mut E
         phasor(constant!({key: None, value: 110.0})),
     )));
 
-    let _ = main.render(elem::engine::ResolvedDirective {
+    let _ = main.render(elem::engine::Directive {
         graph: Some(vec![cycle]),
         resources: None,
     });
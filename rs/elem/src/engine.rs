@@ -1,9 +1,11 @@
-use crate::node::{shallow_clone, NodeRepr, ShallowNodeRepr};
+use crate::node::NodeRepr;
+use crate::queue;
+use crate::reconciler::Reconciler;
 use crate::std::prelude::*;
-use serde_json::json;
+use serde::Deserialize;
 use std::cell::UnsafeCell;
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::collections::VecDeque;
 
 pub trait FloatType: 'static {}
 impl FloatType for f32 {}
@@ -62,12 +64,29 @@ mod ffi {
     }
 }
 
+/// Commands the control thread hands to the audio thread over the command
+/// queue. The audio thread is the sole owner of `EngineInternal` and is the
+/// only thread that ever calls into the C++ runtime.
+enum Command {
+    ApplyInstructions(serde_json::Value),
+    AddResource {
+        name: String,
+        channels: usize,
+        frames: usize,
+        data: Vec<f32>,
+    },
+}
+
 struct EngineInternal {
     inner: UnsafeCell<cxx::UniquePtr<ffi::RuntimeBindings>>,
 }
 
+// `process()` takes `&self` (see `ProcessHandle`, below) so the audio
+// callback closure can remain an `Fn`; the `UnsafeCell` gives interior
+// mutability. This is no longer shared across threads -- only `ProcessHandle`
+// holds one, and only the audio thread ever calls through it -- so `Send` is
+// all that's required to move it into the audio callback once at setup.
 unsafe impl Send for EngineInternal {}
-unsafe impl Sync for EngineInternal {}
 
 impl EngineInternal {
     pub fn add_shared_resource(
@@ -120,12 +139,22 @@ impl EngineInternal {
 }
 
 pub struct ProcessHandle {
-    inner: Arc<EngineInternal>,
+    inner: EngineInternal,
+    commands: queue::Consumer<Command>,
+    events: queue::Producer<serde_json::Value>,
 }
 
 impl ProcessHandle {
-    pub fn new(inner: Arc<EngineInternal>) -> Self {
-        Self { inner }
+    fn new(
+        inner: EngineInternal,
+        commands: queue::Consumer<Command>,
+        events: queue::Producer<serde_json::Value>,
+    ) -> Self {
+        Self {
+            inner,
+            commands,
+            events,
+        }
     }
 
     pub fn process(
@@ -135,6 +164,25 @@ impl ProcessHandle {
         num_channels: usize,
         num_frames: usize,
     ) {
+        // Drain whatever the control thread queued up since the last block
+        // and apply it before rendering, so the audio thread is the only
+        // thread that ever touches the runtime binding.
+        for command in self.commands.drain() {
+            match command {
+                Command::ApplyInstructions(instructions) => {
+                    let _ = self.inner.apply_instructions(&instructions);
+                }
+                Command::AddResource {
+                    name,
+                    channels,
+                    frames,
+                    data,
+                } => {
+                    let _ = self.inner.add_shared_resource(&name, channels, frames, &data);
+                }
+            }
+        }
+
         unsafe {
             self.inner
                 .inner
@@ -145,126 +193,171 @@ impl ProcessHandle {
                 .unwrap()
                 .process(input_data, output_data, num_channels, num_frames);
         }
+
+        if let Ok(serde_json::Value::Array(events)) = self.inner.process_queued_events() {
+            for event in events.into_iter() {
+                // If the control thread has fallen behind, drop the newest
+                // events rather than block the audio thread on a full queue.
+                let _ = self.events.push(event);
+            }
+        }
     }
 }
 
 pub struct MainHandle {
-    inner: Arc<EngineInternal>,
-    node_map: BTreeMap<i32, ShallowNodeRepr>,
+    commands: queue::Producer<Command>,
+    reconciler: Reconciler,
 }
 
 impl MainHandle {
-    pub fn new(inner: Arc<EngineInternal>) -> Self {
+    fn new(commands: queue::Producer<Command>) -> Self {
         Self {
-            inner: inner,
-            node_map: BTreeMap::new(),
+            commands,
+            reconciler: Reconciler::new(),
         }
     }
 
     pub fn reconcile(&mut self, roots: &Vec<NodeRepr>) -> serde_json::Value {
-        let mut visited: HashSet<i32> = HashSet::new();
-        let mut queue: VecDeque<&NodeRepr> = VecDeque::new();
-        let mut instructions = serde_json::Value::Array(vec![]);
+        serde_json::Value::Array(
+            self.reconciler
+                .reconcile(roots)
+                .iter()
+                .map(|instruction| instruction.to_json())
+                .collect(),
+        )
+    }
 
-        for root in roots.iter() {
-            // TODO: ref?
-            queue.push_back(root);
+    /// Render a directive, returning the reconcile instruction batch that was
+    /// queued for the audio thread, so a caller (e.g. the server's WebSocket
+    /// handler) can forward the exact same batch back to the client. `None`
+    /// means the directive carried no graph, so nothing was reconciled.
+    pub fn render(&mut self, directive: Directive) -> Result<Option<serde_json::Value>, &str> {
+        if let Some(resources) = directive.resources {
+            for (k, v) in resources.into_iter() {
+                let command = Command::AddResource {
+                    name: k,
+                    channels: v.channels,
+                    frames: v.frames,
+                    data: v.data,
+                };
+                if self.commands.push(command).is_err() {
+                    println!("Command queue full, dropping resource add");
+                }
+            }
         }
 
-        while !queue.is_empty() {
-            let next = queue.pop_front().unwrap();
-
-            if visited.contains(&next.hash) {
-                continue;
-            }
+        if let Some(graph) = directive.graph {
+            let instructions = self.reconcile(&graph);
 
-            // Mount
-            if !self.node_map.contains_key(&next.hash) {
-                // Create node
-                instructions
-                    .as_array_mut()
-                    .unwrap()
-                    .push(json!([0, next.hash, next.kind]));
-
-                // Append child
-                for child in next.children.iter() {
-                    instructions.as_array_mut().unwrap().push(json!([
-                        2,
-                        next.hash,
-                        child.hash,
-                        child.output_channel
-                    ]));
-                }
+            return match self.commands.push(Command::ApplyInstructions(instructions.clone())) {
+                Ok(()) => Ok(Some(instructions)),
+                Err(_) => Err("command queue full, dropping instruction batch"),
+            };
+        }
 
-                self.node_map.insert(next.hash, shallow_clone(&next));
-            }
+        Ok(None)
+    }
+}
 
-            // Props
-            for (name, value) in &next.props {
-                // TODO: Only add the instruction if the prop value != existing prop value
-                instructions
-                    .as_array_mut()
-                    .unwrap()
-                    .push(json!([3, next.hash, name, value]));
-            }
+/// A strongly-typed engine event, deserialized from the runtime's raw event
+/// JSON. `node` is the hash of the node that emitted the event, where the
+/// runtime reports one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum EngineEvent {
+    Meter { node: i32, value: f64 },
+    Snapshot { node: i32, data: serde_json::Value },
+    Error { message: String },
+    /// Anything the runtime emits that doesn't match a known shape above;
+    /// kept rather than dropped so callers can still observe it.
+    #[serde(other)]
+    Unknown,
+}
 
-            for child in next.children.iter() {
-                queue.push_back(child);
-            }
+/// Async stream of [`EngineEvent`]s drained from the audio thread's event
+/// queue. Replaces the old fixed 30 Hz poll loop: `poll_next` drives its own
+/// `tokio::time::Interval` at whatever cadence the caller configured, so
+/// events surface as soon as the next drain tick fires rather than on an
+/// arbitrary timer baked into the server binary. Callers can `.next().await`
+/// it directly or fold it into their own `select!` loop.
+pub struct EventStream {
+    events: queue::Consumer<serde_json::Value>,
+    interval: tokio::time::Interval,
+    pending: VecDeque<EngineEvent>,
+}
 
-            visited.insert(next.hash);
+impl EventStream {
+    fn new(events: queue::Consumer<serde_json::Value>, cadence: std::time::Duration) -> Self {
+        Self {
+            events,
+            interval: tokio::time::interval(cadence),
+            pending: VecDeque::new(),
         }
+    }
+}
 
-        // Activate roots
-        instructions.as_array_mut().unwrap().push(json!([
-            4,
-            roots.iter().map(|n| n.hash).collect::<Vec<i32>>()
-        ]));
+impl EventStream {
+    /// Synchronously drain whatever events are queued right now, bypassing
+    /// the timer tick entirely. There's no async runtime driving the stream
+    /// during an offline render, so [`render_offline`] pulls events straight
+    /// off the queue after each block instead.
+    pub fn drain_now(&mut self) -> Vec<EngineEvent> {
+        self.events
+            .drain()
+            .into_iter()
+            .map(|raw| serde_json::from_value(raw).unwrap_or(EngineEvent::Unknown))
+            .collect()
+    }
+}
 
-        // Commit
-        instructions.as_array_mut().unwrap().push(json!([5]));
+impl futures_core::Stream for EventStream {
+    type Item = EngineEvent;
 
-        // Sort so that creates land before appends, etc
-        instructions
-            .as_array_mut()
-            .unwrap()
-            .sort_by(|a, b| a[0].as_i64().cmp(&b[0].as_i64()));
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use std::task::Poll;
 
-        instructions
-    }
+        let this = self.get_mut();
 
-    pub fn render(&mut self, directive: Directive) -> Result<i32, &str> {
-        if let Some(resources) = directive.resources {
-            for (k, v) in resources.into_iter() {
-                let rc =
-                    self.inner
-                        .add_shared_resource(&k, v.channels, v.frames, v.data.as_slice());
-                println!("Add resource result: {}", rc);
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(event));
             }
-        }
-
-        if let Some(graph) = directive.graph {
-            let instructions = self.reconcile(&graph);
-            let result = self.inner.apply_instructions(&instructions);
-            println!("Apply instructions result: {}", result.unwrap_or(-1));
 
-            result
-        } else {
-            Ok(0)
+            match this.interval.poll_tick(cx) {
+                Poll::Ready(_) => {
+                    for raw in this.events.drain() {
+                        this.pending.push_back(
+                            serde_json::from_value(raw).unwrap_or(EngineEvent::Unknown),
+                        );
+                    }
+
+                    if this.pending.is_empty() {
+                        continue;
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            }
         }
     }
-
-    pub fn process_queued_events(&mut self) -> Result<serde_json::Value, &str> {
-        self.inner.process_queued_events()
-    }
 }
 
-pub fn new_engine(sample_rate: f64, block_size: usize) -> (MainHandle, ProcessHandle) {
-    let cell = UnsafeCell::new(ffi::new_runtime_instance(sample_rate, block_size));
-    let arc = Arc::new(EngineInternal { inner: cell });
+pub fn new_engine(
+    sample_rate: f64,
+    block_size: usize,
+) -> (MainHandle, ProcessHandle, EventStream) {
+    let inner = EngineInternal {
+        inner: UnsafeCell::new(ffi::new_runtime_instance(sample_rate, block_size)),
+    };
+
+    let (command_tx, command_rx) = queue::channel(64);
+    let (event_tx, event_rx) = queue::channel(256);
 
-    let mut main = MainHandle::new(arc.clone());
-    let proc = ProcessHandle::new(arc.clone());
+    let mut main = MainHandle::new(command_tx);
+    let proc = ProcessHandle::new(inner, command_rx, event_tx);
+    let events = EventStream::new(event_rx, std::time::Duration::from_millis((1000.0 / 30.0) as u64));
 
     let cycle = root(sin(mul2(
         constant!({key: None, value: 2.0 * std::f64::consts::PI}),
@@ -277,5 +370,53 @@ pub fn new_engine(sample_rate: f64, block_size: usize) -> (MainHandle, ProcessHa
         resources: None,
     });
 
-    (main, proc)
+    (main, proc, events)
+}
+
+/// Render a directive without a live audio device: drives `main`/`proc`
+/// through exactly the same reconcile-then-process path the audio callback
+/// uses, just synchronously and in `block_size`-frame chunks, and returns
+/// the fully rendered buffer plus whatever events were collected along the
+/// way. `input` supplies the input buffer fed to each block; pass `None` to
+/// render with silence.
+pub fn render_offline(
+    main: &mut MainHandle,
+    proc: &ProcessHandle,
+    events: &mut EventStream,
+    directive: Directive,
+    num_channels: usize,
+    num_frames: usize,
+    block_size: usize,
+    input: Option<&AudioBuffer<f32>>,
+) -> (AudioBuffer<f32>, Vec<EngineEvent>) {
+    let _ = main.render(directive);
+
+    let mut output = AudioBuffer::<f32>::new(num_channels, num_frames);
+    let silence = vec![0.0f32; num_channels * block_size];
+    let mut collected = Vec::new();
+    let mut frame = 0;
+
+    while frame < num_frames {
+        let this_block = block_size.min(num_frames - frame);
+        let out_start = frame * num_channels;
+        let out_end = out_start + this_block * num_channels;
+        let out_slice = &mut output.data[out_start..out_end];
+
+        let in_slice = match input {
+            Some(buf) => &buf.data[out_start..out_end],
+            None => &silence[..this_block * num_channels],
+        };
+
+        proc.process(
+            in_slice.as_ptr(),
+            out_slice.as_mut_ptr(),
+            num_channels,
+            this_block,
+        );
+        collected.extend(events.drain_now());
+
+        frame += this_block;
+    }
+
+    (output, collected)
 }
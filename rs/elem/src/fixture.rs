@@ -0,0 +1,113 @@
+//! Fixture-driven golden-output testing for offline renders.
+//!
+//! Each fixture names a graph's sample rate, frame count, channel count and
+//! block size, a per-sample tolerance, and a committed golden WAV file
+//! recording the expected output. Running a fixture drives the graph
+//! through [`crate::engine::render_offline`] and fails if any sample drifts
+//! from the golden recording by more than the tolerance. This gives
+//! deterministic regression coverage for `reconcile` and the DSP nodes
+//! without any audio hardware.
+use crate::engine::{render_offline, Directive};
+use crate::node::NodeRepr;
+
+pub struct Fixture {
+    pub sample_rate: f64,
+    pub num_channels: usize,
+    pub num_frames: usize,
+    pub block_size: usize,
+    pub tolerance: f32,
+    pub golden_path: &'static str,
+}
+
+#[derive(Debug)]
+pub enum FixtureError {
+    LengthMismatch { expected: usize, actual: usize },
+    Mismatch { frame: usize, channel: usize, expected: f32, actual: f32 },
+    Io(String),
+}
+
+impl std::fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FixtureError::LengthMismatch { expected, actual } => write!(
+                f,
+                "golden file has {expected} samples, render produced {actual}"
+            ),
+            FixtureError::Mismatch {
+                frame,
+                channel,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "sample mismatch at frame {frame} channel {channel}: expected {expected}, got {actual}"
+            ),
+            FixtureError::Io(e) => write!(f, "failed to read golden file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {}
+
+/// Render `graph` offline per `fixture`'s parameters and compare it, sample
+/// by sample, against the committed golden WAV.
+pub fn run_fixture(fixture: &Fixture, graph: Vec<NodeRepr>) -> Result<(), FixtureError> {
+    let (mut main, proc, mut events) =
+        crate::engine::new_engine(fixture.sample_rate, fixture.block_size);
+
+    let directive = Directive {
+        graph: Some(graph),
+        resources: None,
+    };
+
+    let (output, _events) = render_offline(
+        &mut main,
+        &proc,
+        &mut events,
+        directive,
+        fixture.num_channels,
+        fixture.num_frames,
+        fixture.block_size,
+        None,
+    );
+
+    let golden = load_golden_wav(fixture.golden_path).map_err(FixtureError::Io)?;
+
+    if golden.len() != output.data.len() {
+        return Err(FixtureError::LengthMismatch {
+            expected: golden.len(),
+            actual: output.data.len(),
+        });
+    }
+
+    for (i, (expected, actual)) in golden.iter().zip(output.data.iter()).enumerate() {
+        if (expected - actual).abs() > fixture.tolerance {
+            return Err(FixtureError::Mismatch {
+                frame: i / fixture.num_channels,
+                channel: i % fixture.num_channels,
+                expected: *expected,
+                actual: *actual,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn load_golden_wav(path: &str) -> Result<Vec<f32>, String> {
+    let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+
+    let samples = match reader.spec().sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .filter_map(Result::ok)
+            .collect::<Vec<f32>>(),
+        hound::SampleFormat::Int => reader
+            .samples::<i16>()
+            .filter_map(Result::ok)
+            .map(|s| s as f32 / i16::MAX as f32)
+            .collect::<Vec<f32>>(),
+    };
+
+    Ok(samples)
+}
@@ -0,0 +1,86 @@
+//! A vendored FNV-1a hasher.
+//!
+//! `std::hash::DefaultHasher` is explicitly documented as unstable across
+//! Rust versions and platforms, which is fine for in-process hash maps but
+//! disqualifies it for [`crate::node::create_node`]'s content hash: two
+//! clients on different builds need to land on the same node id for the
+//! same graph. FNV-1a's algorithm is fixed by spec and its offset basis and
+//! prime are constants rather than a per-process random seed, so the output
+//! only ever depends on the bytes fed in. The `Hasher` trait's default
+//! `write_u32`/`write_i32`/etc. methods feed `write` with `to_ne_bytes()` --
+//! native-endian -- which would leak platform byte order into the hash, so
+//! every multi-byte numeric `write_*` method is overridden here to go
+//! through `to_le_bytes()` instead.
+use std::hash::Hasher;
+
+const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+pub struct FnvHasher(u64);
+
+impl FnvHasher {
+    pub fn new() -> Self {
+        Self(OFFSET_BASIS)
+    }
+}
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes.iter() {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    // Cast to a fixed 64-bit width before writing so the hash doesn't also
+    // vary between 32-bit and 64-bit targets.
+    fn write_usize(&mut self, i: usize) {
+        self.write(&(i as u64).to_le_bytes());
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write(&(i as i64).to_le_bytes());
+    }
+}
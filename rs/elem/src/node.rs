@@ -1,5 +1,8 @@
+use crate::fnv::FnvHasher;
 use serde::{Deserialize, Serialize};
-use std::hash::{DefaultHasher, Hash, Hasher};
+use serde_hashkey::to_key_with_ordered_float;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
 
 #[derive(Serialize, Deserialize)]
 pub struct NodeRepr {
@@ -15,17 +18,10 @@ pub fn create_node(
     props: serde_json::Map<String, serde_json::Value>,
     children: Vec<NodeRepr>,
 ) -> NodeRepr {
-    let mut hasher = DefaultHasher::new();
-
-    kind.hash(&mut hasher);
-    props.hash(&mut hasher);
-
-    for child in children.iter() {
-        child.hash.hash(&mut hasher);
-    }
+    let hash = content_hash(kind, &props, &children);
 
     NodeRepr {
-        hash: hasher.finish() as i32,
+        hash,
         kind: kind.to_string(),
         props,
         output_channel: 0,
@@ -33,12 +29,39 @@ pub fn create_node(
     }
 }
 
+/// Hash `kind`, `props` and the children's own hashes into a reproducible
+/// `i32` node id. `props` is canonicalized into a `serde_hashkey::Key` first,
+/// using the ordered-float policy so `-0.0`/`+0.0` collapse to one form and
+/// `NaN` normalizes to a single bit pattern, and so map entries sort by key
+/// before hashing rather than leaking `serde_json::Map`'s iteration order.
+/// The canonical key is then hashed with FNV-1a rather than `DefaultHasher`,
+/// since the result has to match across processes and machines, not just
+/// within one.
+fn content_hash(
+    kind: &str,
+    props: &serde_json::Map<String, serde_json::Value>,
+    children: &[NodeRepr],
+) -> i32 {
+    let key = to_key_with_ordered_float(props).expect("node props must be hashable");
+
+    let mut hasher = FnvHasher::new();
+    kind.hash(&mut hasher);
+    key.hash(&mut hasher);
+
+    for child in children.iter() {
+        child.hash.hash(&mut hasher);
+    }
+
+    hasher.finish() as i32
+}
+
+#[derive(Serialize)]
 pub struct ShallowNodeRepr {
-    hash: i32,
-    kind: String,
-    props: serde_json::Map<String, serde_json::Value>,
-    output_channel: u32,
-    children: Vec<i32>,
+    pub(crate) hash: i32,
+    pub(crate) kind: String,
+    pub(crate) props: serde_json::Map<String, serde_json::Value>,
+    pub(crate) output_channel: u32,
+    pub(crate) children: Vec<i32>,
 }
 
 pub fn shallow_clone(node: &NodeRepr) -> ShallowNodeRepr {
@@ -50,3 +73,143 @@ pub fn shallow_clone(node: &NodeRepr) -> ShallowNodeRepr {
         children: node.children.iter().map(|n| n.hash).collect::<Vec<i32>>(),
     }
 }
+
+/// Flatten `root` into a deduplicated, topologically ordered list of
+/// [`ShallowNodeRepr`]s: a post-order traversal that visits each node's
+/// children before the node itself, and emits each unique `hash` exactly
+/// once no matter how many places in the tree share it. Children always
+/// precede their parents in the result, so a consumer can instantiate nodes
+/// in order and always have a referenced child already available.
+pub fn flatten(root: &NodeRepr) -> Vec<ShallowNodeRepr> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    flatten_into(root, &mut seen, &mut out);
+    out
+}
+
+fn flatten_into(node: &NodeRepr, seen: &mut HashSet<i32>, out: &mut Vec<ShallowNodeRepr>) {
+    if seen.contains(&node.hash) {
+        return;
+    }
+
+    for child in node.children.iter() {
+        flatten_into(child, seen, out);
+    }
+
+    seen.insert(node.hash);
+    out.push(shallow_clone(node));
+}
+
+/// [`flatten`], serialized to the flat JSON batch the renderer consumes.
+pub fn serialize_graph(root: &NodeRepr) -> serde_json::Value {
+    serde_json::to_value(flatten(root)).expect("ShallowNodeRepr is always representable as JSON")
+}
+
+/// Serde `with` helper for buffer props (used by `table`/`sample`): a
+/// `Vec<f32>` of thousands of samples is enormous and slow to parse as a
+/// JSON number array, so this encodes it as a base64 string of
+/// little-endian `f32` bytes instead, and decodes it back losslessly.
+pub mod base64_buffer {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(data: &[f32], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut bytes = Vec::with_capacity(data.len() * 4);
+        for sample in data.iter() {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        base64::engine::general_purpose::STANDARD
+            .encode(bytes)
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<f32>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(serde::de::Error::custom)?;
+
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn props(entries: &[(&str, serde_json::Value)]) -> serde_json::Map<String, serde_json::Value> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn negative_and_positive_zero_hash_the_same() {
+        let negative = create_node("const", props(&[("value", serde_json::json!(-0.0))]), vec![]);
+        let positive = create_node("const", props(&[("value", serde_json::json!(0.0))]), vec![]);
+
+        assert_eq!(negative.hash, positive.hash);
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct BufferWrapper {
+        #[serde(with = "crate::node::base64_buffer")]
+        data: Vec<f32>,
+    }
+
+    #[test]
+    fn buffer_round_trips_losslessly_through_base64() {
+        let data = vec![0.0f32, -0.0, 1.0, -1.0, f32::MAX, f32::MIN_POSITIVE, f32::NAN];
+        let wrapper = BufferWrapper { data: data.clone() };
+
+        let json = serde_json::to_value(&wrapper).unwrap();
+        assert!(json["data"].is_string(), "buffer must serialize as a base64 string, not a number array");
+
+        let round_tripped: BufferWrapper = serde_json::from_value(json).unwrap();
+
+        assert_eq!(round_tripped.data.len(), data.len());
+        for (original, decoded) in data.iter().zip(round_tripped.data.iter()) {
+            if original.is_nan() {
+                assert!(decoded.is_nan());
+            } else {
+                // Compare bit patterns rather than `==` so `-0.0` and `0.0`,
+                // which are `==`-equal but distinct bit patterns, are still
+                // caught if the encoder/decoder ever confused them.
+                assert_eq!(original.to_bits(), decoded.to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn prop_order_does_not_affect_the_hash() {
+        let a = create_node(
+            "table",
+            props(&[
+                ("key", serde_json::json!("voice-1")),
+                ("data", serde_json::json!("AAAAAA==")),
+            ]),
+            vec![],
+        );
+        let b = create_node(
+            "table",
+            props(&[
+                ("data", serde_json::json!("AAAAAA==")),
+                ("key", serde_json::json!("voice-1")),
+            ]),
+            vec![],
+        );
+
+        assert_eq!(a.hash, b.hash);
+    }
+}
@@ -0,0 +1,174 @@
+//! A wait-free single-producer/single-consumer ring buffer.
+//!
+//! `MainHandle::render` (driven from a Tokio task) and `ProcessHandle::process`
+//! (driven from the audio callback) used to share one `UnsafeCell` around the
+//! C++ runtime and mutate it concurrently with no synchronization at all --
+//! a data race that could glitch or crash the audio output under load.
+//! Routing control-thread work through a queue instead means the audio
+//! thread is the only thread that ever touches the runtime binding, and the
+//! control thread never blocks the audio thread on a mutex.
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Slot<T> {
+    value: UnsafeCell<Option<T>>,
+}
+
+unsafe impl<T: Send> Sync for Slot<T> {}
+
+struct Shared<T> {
+    slots: Box<[Slot<T>]>,
+    // Indices into `slots`, mod capacity. `head` is only ever written by the
+    // consumer, `tail` only ever written by the producer.
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+unsafe impl<T: Send> Send for Producer<T> {}
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+/// Build a bounded SPSC channel. `capacity` is the number of batches that may
+/// be queued between control-thread pushes and the next audio block; one
+/// slot is always kept empty to distinguish full from empty.
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let slots = (0..(capacity.max(1) + 1))
+        .map(|_| Slot {
+            value: UnsafeCell::new(None),
+        })
+        .collect::<Vec<_>>()
+        .into_boxed_slice();
+
+    let shared = Arc::new(Shared {
+        slots,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+
+    (
+        Producer {
+            shared: shared.clone(),
+        },
+        Consumer { shared },
+    )
+}
+
+impl<T> Producer<T> {
+    /// Push a value onto the queue. Returns the value back if the queue is
+    /// full so the caller can decide how to handle backpressure -- the
+    /// control thread must never block waiting on the audio thread to catch
+    /// up.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % self.shared.slots.len();
+
+        if next == self.shared.head.load(Ordering::Acquire) {
+            return Err(value);
+        }
+
+        unsafe {
+            *self.shared.slots[tail].value.get() = Some(value);
+        }
+        self.shared.tail.store(next, Ordering::Release);
+
+        Ok(())
+    }
+}
+
+impl<T> Consumer<T> {
+    pub fn pop(&self) -> Option<T> {
+        let head = self.shared.head.load(Ordering::Relaxed);
+
+        if head == self.shared.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let value = unsafe { (*self.shared.slots[head].value.get()).take() };
+        self.shared
+            .head
+            .store((head + 1) % self.shared.slots.len(), Ordering::Release);
+
+        value
+    }
+
+    /// Drain every value currently queued, in FIFO order.
+    pub fn drain(&self) -> Vec<T> {
+        let mut out = Vec::new();
+        while let Some(value) = self.pop() {
+            out.push(value);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pop_on_empty_queue_returns_none() {
+        let (_tx, rx) = channel::<i32>(4);
+        assert_eq!(rx.pop(), None);
+    }
+
+    #[test]
+    fn push_past_capacity_is_rejected_and_returns_the_value() {
+        let (tx, _rx) = channel::<i32>(2);
+
+        assert!(tx.push(1).is_ok());
+        assert!(tx.push(2).is_ok());
+        assert_eq!(tx.push(3), Err(3));
+    }
+
+    #[test]
+    fn drain_yields_values_in_fifo_order() {
+        let (tx, rx) = channel::<i32>(4);
+
+        tx.push(1).unwrap();
+        tx.push(2).unwrap();
+        tx.push(3).unwrap();
+
+        assert_eq!(rx.drain(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn fifo_order_holds_across_a_wrap_of_the_ring_buffer() {
+        let (tx, rx) = channel::<i32>(2);
+
+        // Push/pop enough times to walk `head`/`tail` all the way around the
+        // ring more than once, interleaving so the buffer never needs to
+        // hold more than its capacity at once.
+        for round in 0..5 {
+            tx.push(round * 2).unwrap();
+            tx.push(round * 2 + 1).unwrap();
+
+            assert_eq!(rx.pop(), Some(round * 2));
+            assert_eq!(rx.pop(), Some(round * 2 + 1));
+        }
+
+        assert_eq!(rx.pop(), None);
+    }
+
+    #[test]
+    fn queue_accepts_pushes_again_after_draining_past_a_wrap() {
+        let (tx, rx) = channel::<i32>(2);
+
+        tx.push(1).unwrap();
+        tx.push(2).unwrap();
+        assert_eq!(tx.push(3), Err(3));
+
+        assert_eq!(rx.drain(), vec![1, 2]);
+
+        tx.push(4).unwrap();
+        tx.push(5).unwrap();
+        assert_eq!(rx.drain(), vec![4, 5]);
+    }
+}
@@ -0,0 +1,203 @@
+//! Diffs a `NodeRepr` tree against the previously committed graph and
+//! produces an ordered, typed instruction batch instead of a positional JSON
+//! array. This crate stores a stable `hash` per `NodeRepr` and models
+//! children as child hashes via `ShallowNodeRepr` -- exactly the
+//! content-addressed representation a reconciler needs to skip re-emitting
+//! nodes whose hash hasn't changed.
+use crate::node::{shallow_clone, NodeRepr, ShallowNodeRepr};
+use crate::schema;
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    CreateNode {
+        hash: i32,
+        kind: String,
+    },
+    RemoveNode {
+        hash: i32,
+    },
+    AppendChild {
+        parent: i32,
+        child: i32,
+        output_channel: u32,
+    },
+    SetProperty {
+        hash: i32,
+        key: String,
+        value: serde_json::Value,
+    },
+    ActivateRoots {
+        hashes: Vec<i32>,
+    },
+    Commit,
+}
+
+impl Instruction {
+    /// Lower to the positional `[opcode, ...]` JSON array the runtime's
+    /// `apply_instructions` expects.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Instruction::CreateNode { hash, kind } => json!([0, hash, kind]),
+            Instruction::RemoveNode { hash } => json!([1, hash]),
+            Instruction::AppendChild {
+                parent,
+                child,
+                output_channel,
+            } => json!([2, parent, child, output_channel]),
+            Instruction::SetProperty { hash, key, value } => json!([3, hash, key, value]),
+            Instruction::ActivateRoots { hashes } => json!([4, hashes]),
+            Instruction::Commit => json!([5]),
+        }
+    }
+}
+
+/// Holds the set of node hashes retained from the previously committed
+/// graph and diffs new graphs against it.
+#[derive(Default)]
+pub struct Reconciler {
+    node_map: BTreeMap<i32, ShallowNodeRepr>,
+}
+
+impl Reconciler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Diff `roots` against the previously committed graph. For every hash
+    /// not seen before, emits `CreateNode` followed by `SetProperty` for
+    /// each prop and `AppendChild` for each child -- children are always
+    /// emitted before their parent (post-order), so a consumer applying
+    /// instructions strictly in order never references a node it hasn't
+    /// created yet. Nodes whose hash is unchanged are skipped entirely.
+    /// Anything retained from the previous pass that's no longer reachable
+    /// is torn down with `RemoveNode`. The batch ends with `ActivateRoots`
+    /// and a trailing `Commit`.
+    pub fn reconcile(&mut self, roots: &[NodeRepr]) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+        let mut visited: HashSet<i32> = HashSet::new();
+        let mut rejected: HashSet<i32> = HashSet::new();
+
+        // A rejected root is dropped from `ActivateRoots` entirely, rather
+        // than activating a root whose `CreateNode` was never emitted.
+        let accepted_roots: Vec<i32> = roots
+            .iter()
+            .filter(|root| self.visit(root, &mut visited, &mut rejected, &mut instructions))
+            .map(|root| root.hash)
+            .collect();
+
+        let stale: Vec<i32> = self
+            .node_map
+            .keys()
+            .filter(|hash| !visited.contains(hash))
+            .copied()
+            .collect();
+
+        for hash in stale {
+            instructions.push(Instruction::RemoveNode { hash });
+            self.node_map.remove(&hash);
+        }
+
+        instructions.push(Instruction::ActivateRoots {
+            hashes: accepted_roots,
+        });
+        instructions.push(Instruction::Commit);
+
+        instructions
+    }
+
+    /// Visit `node`, returning whether it (and its whole subtree) was
+    /// accepted. A node that fails schema validation, or that has any
+    /// rejected child, is rejected itself: no `CreateNode`/`SetProperty` is
+    /// emitted for it and its parent skips the `AppendChild` that would
+    /// otherwise reference a node the runtime never created.
+    fn visit(
+        &mut self,
+        node: &NodeRepr,
+        visited: &mut HashSet<i32>,
+        rejected: &mut HashSet<i32>,
+        out: &mut Vec<Instruction>,
+    ) -> bool {
+        if rejected.contains(&node.hash) {
+            return false;
+        }
+
+        if visited.contains(&node.hash) {
+            return true;
+        }
+
+        let existing_props = self.node_map.get(&node.hash).map(|n| n.props.clone());
+
+        // A retained node was already validated the reconcile that first
+        // created it; an unchanged hash means unchanged kind/props/children,
+        // so there's nothing new to validate. `schema::validate` only checks
+        // this node, not its subtree -- the post-order walk below visits
+        // every descendant itself, so the whole tree still gets covered.
+        if existing_props.is_none() {
+            if let Err(e) = schema::validate(node) {
+                println!("Rejecting malformed graph at node {}: {}", node.hash, e);
+                rejected.insert(node.hash);
+                return false;
+            }
+        }
+
+        // Post-order: a node's children are fully created (and their own
+        // children appended) before the node itself is created.
+        let mut children_accepted = true;
+        for child in node.children.iter() {
+            if !self.visit(child, visited, rejected, out) {
+                children_accepted = false;
+            }
+        }
+
+        if !children_accepted {
+            println!(
+                "Rejecting node {} because a child node was rejected",
+                node.hash
+            );
+            rejected.insert(node.hash);
+            return false;
+        }
+
+        if existing_props.is_none() {
+            out.push(Instruction::CreateNode {
+                hash: node.hash,
+                kind: node.kind.clone(),
+            });
+
+            for child in node.children.iter() {
+                out.push(Instruction::AppendChild {
+                    parent: node.hash,
+                    child: child.hash,
+                    output_channel: child.output_channel,
+                });
+            }
+        }
+
+        // Only emit a SetProperty instruction when the value actually
+        // changed since the last reconcile, so unchanged graphs don't
+        // re-send a full prop batch on every render.
+        for (key, value) in node.props.iter() {
+            let unchanged = existing_props
+                .as_ref()
+                .and_then(|props| props.get(key))
+                .is_some_and(|existing| existing == value);
+
+            if !unchanged {
+                out.push(Instruction::SetProperty {
+                    hash: node.hash,
+                    key: key.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+
+        if existing_props.is_none() {
+            self.node_map.insert(node.hash, shallow_clone(node));
+        }
+
+        visited.insert(node.hash);
+        true
+    }
+}
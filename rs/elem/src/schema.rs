@@ -0,0 +1,298 @@
+//! Declarative schema definitions for node kinds.
+//!
+//! Previously every builder in `std::primitives` hand-called `create_node`
+//! with a hard-coded kind string and ad-hoc props, which meant adding one of
+//! the dozens of node kinds the runtime supports (filters, delays, tables,
+//! sample players, FFT, ...) was pure boilerplate and nothing validated prop
+//! names or child arity. `define_nodes!` describes each kind once --- its
+//! kind string, prop names/types, child arity, whether it takes a `key` ---
+//! and expands that description into both a `NodeSchema` entry consulted by
+//! [`validate`] and the typed builder function callers use. This mirrors the
+//! schema-plus-codegen approach Preserves schemas use: describe the shape
+//! once, generate the rest.
+use crate::node::{create_node, NodeRepr};
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropType {
+    Float,
+    Int,
+    String,
+    Bool,
+    /// A buffer prop, carried as a base64-encoded string of little-endian
+    /// `f32` samples rather than a JSON number array (see
+    /// `crate::node::base64_buffer`).
+    Buffer,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PropSchema {
+    pub name: &'static str,
+    pub ty: PropType,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NodeSchema {
+    pub kind: &'static str,
+    pub props: &'static [PropSchema],
+    pub min_children: usize,
+    pub max_children: usize,
+    pub has_key: bool,
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    UnknownKind(String),
+    ChildArity {
+        kind: String,
+        expected: (usize, usize),
+        actual: usize,
+    },
+    MissingProp {
+        kind: String,
+        prop: &'static str,
+    },
+    PropType {
+        kind: String,
+        prop: &'static str,
+        expected: PropType,
+    },
+    UnsupportedKey {
+        kind: String,
+    },
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaError::UnknownKind(kind) => write!(f, "unknown node kind `{kind}`"),
+            SchemaError::ChildArity {
+                kind,
+                expected: (min, max),
+                actual,
+            } => write!(
+                f,
+                "node `{kind}` expects between {min} and {max} children, got {actual}"
+            ),
+            SchemaError::MissingProp { kind, prop } => {
+                write!(f, "node `{kind}` is missing required prop `{prop}`")
+            }
+            SchemaError::PropType { kind, prop, expected } => write!(
+                f,
+                "node `{kind}` prop `{prop}` expected a {expected:?} value"
+            ),
+            SchemaError::UnsupportedKey { kind } => {
+                write!(f, "node `{kind}` does not accept a `key` prop")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+macro_rules! define_nodes {
+    ($(
+        $schema:ident {
+            kind: $kind:literal,
+            props: [$($pname:literal : $pty:expr => $preq:literal),* $(,)?],
+            children: ($min:expr, $max:expr),
+            has_key: $has_key:literal,
+        }
+        fn $fn_name:ident($($arg:ident : $argty:ty),* $(,)?) -> NodeRepr {
+            props: $build_props:expr,
+            children: $build_children:expr,
+        }
+    )*) => {
+        $(
+            pub static $schema: NodeSchema = NodeSchema {
+                kind: $kind,
+                props: &[$(PropSchema { name: $pname, ty: $pty, required: $preq }),*],
+                min_children: $min,
+                max_children: $max,
+                has_key: $has_key,
+            };
+
+            pub fn $fn_name($($arg: $argty),*) -> NodeRepr {
+                create_node($kind, $build_props, $build_children)
+            }
+        )*
+    };
+}
+
+define_nodes! {
+    ROOT_SCHEMA {
+        kind: "root",
+        props: [],
+        children: (1, 1),
+        has_key: false,
+    }
+    fn root(x: NodeRepr) -> NodeRepr {
+        props: json!({"channel": 0.0}).as_object().unwrap().clone(),
+        children: vec![x],
+    }
+
+    SIN_SCHEMA {
+        kind: "sin",
+        props: [],
+        children: (1, 1),
+        has_key: false,
+    }
+    fn sin(x: NodeRepr) -> NodeRepr {
+        props: Default::default(),
+        children: vec![x],
+    }
+
+    MUL_SCHEMA {
+        kind: "mul",
+        props: [],
+        children: (2, 2),
+        has_key: false,
+    }
+    fn mul2(x: NodeRepr, y: NodeRepr) -> NodeRepr {
+        props: Default::default(),
+        children: vec![x, y],
+    }
+
+    PHASOR_SCHEMA {
+        kind: "phasor",
+        props: [],
+        children: (1, 1),
+        has_key: false,
+    }
+    fn phasor(rate: NodeRepr) -> NodeRepr {
+        props: Default::default(),
+        children: vec![rate],
+    }
+}
+
+// `const` keeps its own hand-written builder (see `std::primitives::constant`)
+// since its props come from a typed `ConstNodeProps` struct rather than a
+// fixed literal, but it's still described here so validation covers it.
+pub static CONST_SCHEMA: NodeSchema = NodeSchema {
+    kind: "const",
+    props: &[PropSchema {
+        name: "value",
+        ty: PropType::Float,
+        required: true,
+    }],
+    min_children: 0,
+    max_children: 0,
+    has_key: true,
+};
+
+// `table`/`sample` share `const`'s shape: a hand-written builder over a
+// typed props struct (see `std::primitives::BufferNodeProps`) rather than a
+// `define_nodes!` literal, since their `data` prop is a `Vec<f32>` buffer
+// and not something a `json!` literal can express.
+pub static TABLE_SCHEMA: NodeSchema = NodeSchema {
+    kind: "table",
+    props: &[PropSchema {
+        name: "data",
+        ty: PropType::Buffer,
+        required: true,
+    }],
+    min_children: 1,
+    max_children: 1,
+    has_key: true,
+};
+
+pub static SAMPLE_SCHEMA: NodeSchema = NodeSchema {
+    kind: "sample",
+    props: &[PropSchema {
+        name: "data",
+        ty: PropType::Buffer,
+        required: true,
+    }],
+    min_children: 1,
+    max_children: 1,
+    has_key: true,
+};
+
+pub static NODE_SCHEMAS: &[&NodeSchema] = &[
+    &ROOT_SCHEMA,
+    &SIN_SCHEMA,
+    &MUL_SCHEMA,
+    &PHASOR_SCHEMA,
+    &CONST_SCHEMA,
+    &TABLE_SCHEMA,
+    &SAMPLE_SCHEMA,
+];
+
+pub fn lookup(kind: &str) -> Option<&'static NodeSchema> {
+    NODE_SCHEMAS.iter().copied().find(|s| s.kind == kind)
+}
+
+/// Validate a single node -- its own kind, child arity, prop presence/types
+/// and `key` prop -- against the schema registry. Does not recurse into
+/// children: `Reconciler::visit` already walks the whole tree post-order and
+/// calls this once per node, so recursing here too would re-validate every
+/// descendant once per ancestor on the way up.
+pub fn validate(node: &NodeRepr) -> Result<(), SchemaError> {
+    let schema = schema_for(node)?;
+
+    if node.children.len() < schema.min_children || node.children.len() > schema.max_children {
+        return Err(SchemaError::ChildArity {
+            kind: node.kind.clone(),
+            expected: (schema.min_children, schema.max_children),
+            actual: node.children.len(),
+        });
+    }
+
+    for prop in schema.props.iter() {
+        // `Option<T>` prop fields (e.g. `const`/`table`/`sample`'s `key`)
+        // serialize a `None` as JSON `null` rather than omitting the key
+        // entirely, so treat a present-but-null value the same as absent.
+        match node.props.get(prop.name).filter(|v| !v.is_null()) {
+            Some(value) if !matches_type(value, prop.ty) => {
+                return Err(SchemaError::PropType {
+                    kind: node.kind.clone(),
+                    prop: prop.name,
+                    expected: prop.ty,
+                });
+            }
+            None if prop.required => {
+                return Err(SchemaError::MissingProp {
+                    kind: node.kind.clone(),
+                    prop: prop.name,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    match node.props.get("key").filter(|v| !v.is_null()) {
+        Some(_) if !schema.has_key => {
+            return Err(SchemaError::UnsupportedKey {
+                kind: node.kind.clone(),
+            });
+        }
+        Some(key) if !matches_type(key, PropType::String) => {
+            return Err(SchemaError::PropType {
+                kind: node.kind.clone(),
+                prop: "key",
+                expected: PropType::String,
+            });
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn matches_type(value: &serde_json::Value, ty: PropType) -> bool {
+    match ty {
+        PropType::Float => value.is_number(),
+        PropType::Int => value.as_i64().is_some() || value.as_u64().is_some(),
+        PropType::String => value.is_string(),
+        PropType::Bool => value.is_boolean(),
+        // Buffers travel as a base64 string (see `crate::node::base64_buffer`),
+        // so a plain JSON number/array in a buffer prop's place is rejected
+        // the same way a wrong-typed string/bool prop would be.
+        PropType::Buffer => value.is_string(),
+    }
+}
+
+fn schema_for(node: &NodeRepr) -> Result<&'static NodeSchema, SchemaError> {
+    lookup(&node.kind).ok_or_else(|| SchemaError::UnknownKind(node.kind.clone()))
+}
@@ -1,26 +1,9 @@
 use crate::node::{create_node, NodeRepr};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 
-pub fn root(x: NodeRepr) -> NodeRepr {
-    create_node(
-        "root",
-        json!({"channel": 0.0}).as_object().unwrap().clone(),
-        vec![x],
-    )
-}
-
-pub fn sin(x: NodeRepr) -> NodeRepr {
-    create_node("sin", Default::default(), vec![x])
-}
-
-pub fn mul2(x: NodeRepr, y: NodeRepr) -> NodeRepr {
-    create_node("mul", Default::default(), vec![x, y])
-}
-
-pub fn phasor(rate: NodeRepr) -> NodeRepr {
-    create_node("phasor", Default::default(), vec![rate])
-}
+// `root`/`sin`/`mul2`/`phasor` are generated from their schema definitions in
+// `crate::schema` rather than hand-written here.
+pub use crate::schema::{mul2, phasor, root, sin};
 
 #[derive(Serialize, Deserialize)]
 pub struct ConstNodeProps {
@@ -55,3 +38,35 @@ macro_rules! constant {
 }
 
 pub use crate::constant;
+
+// `table`/`sample` carry a `Vec<f32>` buffer prop, so -- like `const` above --
+// they get a hand-written builder over a typed props struct rather than a
+// `define_nodes!` literal; `data` is serialized through `base64_buffer`
+// instead of a JSON number array so wavetable/sample-sized buffers stay
+// cheap to ship and parse.
+#[derive(Serialize, Deserialize)]
+pub struct BufferNodeProps {
+    pub key: Option<String>,
+    #[serde(with = "crate::node::base64_buffer")]
+    pub data: Vec<f32>,
+}
+
+fn buffer_props(props: &BufferNodeProps) -> serde_json::Map<String, serde_json::Value> {
+    serde_json::to_value(props)
+        .unwrap()
+        .as_object()
+        .unwrap()
+        .clone()
+}
+
+/// A wavetable lookup: reads `props.data` at the position given by `index`,
+/// a signal in `[0, 1)`.
+pub fn table(props: &BufferNodeProps, index: NodeRepr) -> NodeRepr {
+    create_node("table", buffer_props(props), vec![index])
+}
+
+/// A one-shot/looping sample player: reads `props.data` by a trigger-driven
+/// playback position.
+pub fn sample(props: &BufferNodeProps, trigger: NodeRepr) -> NodeRepr {
+    create_node("sample", buffer_props(props), vec![trigger])
+}
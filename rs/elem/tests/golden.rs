@@ -0,0 +1,21 @@
+use elem::fixture::{run_fixture, Fixture};
+use elem::std::prelude::*;
+
+#[test]
+fn sine_440_matches_golden() {
+    let fixture = Fixture {
+        sample_rate: 44100.0,
+        num_channels: 1,
+        num_frames: 512,
+        block_size: 128,
+        tolerance: 1e-3,
+        golden_path: concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sine_440.wav"),
+    };
+
+    let graph = vec![root(sin(mul2(
+        constant!({key: None, value: 2.0 * std::f64::consts::PI}),
+        phasor(constant!({key: None, value: 440.0})),
+    )))];
+
+    run_fixture(&fixture, graph).expect("offline render should match golden output");
+}
@@ -0,0 +1,127 @@
+//! Pluggable, streaming resource decoding.
+//!
+//! `decode` sniffs a handful of leading bytes off of any `BufRead` (a local
+//! file, or a streamed HTTP response body) to pick the right
+//! [`ResourceDecoder`], then decodes straight off that same reader rather
+//! than requiring the whole resource to be buffered into memory first.
+use elem::engine::AudioBuffer;
+use std::io::BufRead;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    UnknownFormat,
+    Wav(String),
+    Flac(String),
+    Fetch(String),
+    Io(String),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::UnknownFormat => write!(f, "unrecognized resource format"),
+            DecodeError::Wav(e) => write!(f, "wav decode error: {e}"),
+            DecodeError::Flac(e) => write!(f, "flac decode error: {e}"),
+            DecodeError::Fetch(e) => write!(f, "fetch error: {e}"),
+            DecodeError::Io(e) => write!(f, "io error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub trait ResourceDecoder: Send + Sync {
+    /// Does this decoder recognize the resource from its leading bytes?
+    fn sniff(&self, prefix: &[u8]) -> bool;
+
+    fn decode(&self, reader: &mut dyn BufRead) -> Result<AudioBuffer<f32>, DecodeError>;
+}
+
+pub struct WavDecoder;
+
+impl ResourceDecoder for WavDecoder {
+    fn sniff(&self, prefix: &[u8]) -> bool {
+        prefix.len() >= 4 && &prefix[0..4] == b"RIFF"
+    }
+
+    fn decode(&self, reader: &mut dyn BufRead) -> Result<AudioBuffer<f32>, DecodeError> {
+        let mut wav = hound::WavReader::new(reader).map_err(|e| DecodeError::Wav(e.to_string()))?;
+        let spec = wav.spec();
+        let num_channels = spec.channels as usize;
+
+        let data: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => wav
+                .samples::<f32>()
+                .collect::<Result<_, _>>()
+                .map_err(|e| DecodeError::Wav(e.to_string()))?,
+            hound::SampleFormat::Int => {
+                // Signed PCM is centered on zero: normalize by the magnitude
+                // of the most negative representable value (2^(bits-1)), not
+                // `2^bits - 1`, which skews every sample toward +1.0.
+                let scale = (1i64 << (spec.bits_per_sample - 1)) as f64;
+                wav.samples::<i32>()
+                    .map(|s| s.map(|v| (v as f64 / scale) as f32))
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| DecodeError::Wav(e.to_string()))?
+            }
+        };
+
+        let num_frames = data.len() / num_channels.max(1);
+        Ok(AudioBuffer {
+            data,
+            channels: num_channels,
+            frames: num_frames,
+        })
+    }
+}
+
+pub struct FlacDecoder;
+
+impl ResourceDecoder for FlacDecoder {
+    fn sniff(&self, prefix: &[u8]) -> bool {
+        prefix.len() >= 4 && &prefix[0..4] == b"fLaC"
+    }
+
+    fn decode(&self, reader: &mut dyn BufRead) -> Result<AudioBuffer<f32>, DecodeError> {
+        let mut flac =
+            claxon::FlacReader::new(reader).map_err(|e| DecodeError::Flac(e.to_string()))?;
+        let info = flac.streaminfo();
+        let num_channels = info.channels as usize;
+        let scale = (1i64 << (info.bits_per_sample - 1)) as f64;
+
+        let data = flac
+            .samples()
+            .map(|s| s.map(|v| (v as f64 / scale) as f32))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DecodeError::Flac(e.to_string()))?;
+
+        let num_frames = data.len() / num_channels.max(1);
+        Ok(AudioBuffer {
+            data,
+            channels: num_channels,
+            frames: num_frames,
+        })
+    }
+}
+
+fn decoders() -> Vec<Box<dyn ResourceDecoder>> {
+    vec![Box::new(WavDecoder), Box::new(FlacDecoder)]
+}
+
+/// Sniff `reader`'s leading bytes and dispatch to the first decoder that
+/// recognizes them, decoding directly off `reader` rather than buffering the
+/// resource whole first.
+pub fn decode(reader: &mut dyn BufRead) -> Result<AudioBuffer<f32>, DecodeError> {
+    let prefix = reader
+        .fill_buf()
+        .map_err(|e| DecodeError::Io(e.to_string()))?
+        .to_vec();
+
+    for decoder in decoders() {
+        if decoder.sniff(&prefix) {
+            return decoder.decode(reader);
+        }
+    }
+
+    Err(DecodeError::UnknownFormat)
+}
@@ -1,56 +1,71 @@
 use elem::{engine::AudioBuffer, node::NodeRepr};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io::BufReader;
+
+pub mod decode;
+pub mod wire;
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct UnresolvedDirective {
     pub graph: Option<Vec<NodeRepr>>,
+    /// Name -> either a local filesystem path or an `http(s)://` URL.
     pub resources: Option<HashMap<String, String>>,
 }
 
-fn decode_audio_data(data: &Vec<u8>) -> Option<AudioBuffer<f32>> {
-    use hound;
-
-    let mut reader = hound::WavReader::new(data.as_slice()).unwrap();
-    let bit_depth = reader.spec().bits_per_sample as f64;
-    dbg!(reader.spec().sample_rate);
-    let interleaved_buffer = reader
-        .samples::<i32>()
-        .map(|x| x.unwrap() as f64 / (2.0f64.powf(bit_depth) - 1.0))
-        .collect::<Vec<f64>>();
-    let num_channels = reader.spec().channels as usize;
-    let num_frames = (reader.len() as usize) / num_channels;
-
-    Some(AudioBuffer::<f32> {
-        data: interleaved_buffer
-            .into_iter()
-            .map(|x| x as f32)
-            .collect::<Vec<f32>>(),
-        channels: num_channels,
-        frames: num_frames,
-    })
+/// Fetch and decode a single resource entry. Local paths are read off disk;
+/// `http(s)://` locations are fetched and decoded as a stream rather than
+/// buffered whole.
+async fn resolve_resource(location: &str) -> Result<AudioBuffer<f32>, decode::DecodeError> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        let url = location.to_string();
+        tokio::task::spawn_blocking(move || {
+            let response =
+                reqwest::blocking::get(&url).map_err(|e| decode::DecodeError::Fetch(e.to_string()))?;
+            let mut reader = BufReader::new(response);
+            decode::decode(&mut reader)
+        })
+        .await
+        .map_err(|e| decode::DecodeError::Fetch(e.to_string()))?
+    } else {
+        let path = location.to_string();
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::open(&path).map_err(|e| decode::DecodeError::Io(e.to_string()))?;
+            let mut reader = BufReader::new(file);
+            decode::decode(&mut reader)
+        })
+        .await
+        .map_err(|e| decode::DecodeError::Io(e.to_string()))?
+    }
 }
 
 async fn resolve_resources(
     resources: &HashMap<String, String>,
-) -> HashMap<String, AudioBuffer<f32>> {
+) -> Result<HashMap<String, AudioBuffer<f32>>, String> {
     let mut result = HashMap::new();
 
-    for (name, path) in resources.iter() {
-        if let Ok(contents) = tokio::fs::read(path).await {
-            let _ = result.insert(name.clone(), decode_audio_data(&contents).unwrap());
-        }
+    for (name, location) in resources.iter() {
+        let buffer = resolve_resource(location)
+            .await
+            .map_err(|e| format!("resource `{name}` ({location}): {e}"))?;
+        result.insert(name.clone(), buffer);
     }
 
-    result
+    Ok(result)
 }
 
-pub async fn resolve_directive(directive: UnresolvedDirective) -> elem::engine::Directive {
-    elem::engine::Directive {
+/// Resolve an `UnresolvedDirective`'s resources into loaded audio buffers.
+/// Errors propagate here rather than panicking, so a malformed or
+/// unreachable resource reports back over the WebSocket instead of killing
+/// the connection task.
+pub async fn resolve_directive(directive: UnresolvedDirective) -> Result<elem::engine::Directive, String> {
+    let resources = match directive.resources {
+        None => None,
+        Some(entries) => Some(resolve_resources(&entries).await?),
+    };
+
+    Ok(elem::engine::Directive {
         graph: directive.graph,
-        resources: match directive.resources {
-            None => None,
-            Some(rs) => Some(resolve_resources(&rs).await),
-        },
-    }
+        resources,
+    })
 }
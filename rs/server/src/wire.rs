@@ -0,0 +1,225 @@
+//! Binary wire encoding for the directive/instruction protocol.
+//!
+//! The WebSocket handler in the `cli` binary accepts both text frames (JSON,
+//! as before) and binary frames. Binary frames are decoded/encoded as
+//! [Preserves](https://preserves.dev), a self-describing value format with a
+//! canonical binary encoding: records (a label plus ordered fields),
+//! sequences, dictionaries, sets, signed integers, floats, strings and byte
+//! strings. Preserves lets a reconcile instruction be a labelled record
+//! (`<create-node 123 "sin">`) instead of a positional JSON array, and lets
+//! resource buffers travel as byte strings inline rather than out-of-band
+//! file paths.
+use crate::UnresolvedDirective;
+use preserves::value::{BinarySource, IOValue, NestedValue, Record, Value};
+
+/// Which encoding a frame carries. WebSocket text frames are always JSON;
+/// binary frames are always Preserves. Callers pick the variant from the
+/// frame type they received off the socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    Json,
+    Preserves,
+}
+
+#[derive(Debug)]
+pub enum WireError {
+    Json(serde_json::Error),
+    Preserves(String),
+}
+
+impl std::fmt::Display for WireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireError::Json(e) => write!(f, "json decode error: {e}"),
+            WireError::Preserves(e) => write!(f, "preserves decode error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+/// Decode a directive from either a JSON text frame or a Preserves binary
+/// frame.
+pub fn decode_directive(format: WireFormat, bytes: &[u8]) -> Result<UnresolvedDirective, WireError> {
+    match format {
+        WireFormat::Json => serde_json::from_slice(bytes).map_err(WireError::Json),
+        WireFormat::Preserves => {
+            let value = parse_preserves(bytes)?;
+            directive_from_preserves(&value)
+        }
+    }
+}
+
+/// Encode the reconcile instruction batch (as produced by
+/// `elem::engine::MainHandle::reconcile`) for the wire.
+pub fn encode_instructions(format: WireFormat, instructions: &serde_json::Value) -> Vec<u8> {
+    match format {
+        WireFormat::Json => instructions.to_string().into_bytes(),
+        WireFormat::Preserves => {
+            let value = instructions_to_preserves(instructions);
+            value.binary_source().into_vec()
+        }
+    }
+}
+
+fn parse_preserves(bytes: &[u8]) -> Result<IOValue, WireError> {
+    IOValue::from_binary_vec(bytes.to_vec()).map_err(|e| WireError::Preserves(e.to_string()))
+}
+
+fn directive_from_preserves(value: &IOValue) -> Result<UnresolvedDirective, WireError> {
+    // A directive arrives as a `<directive graph resources>` record, where
+    // `graph` and `resources` are themselves Preserves-encoded JSON-ish
+    // values produced by the client's own encoder. We bounce through
+    // serde_json::Value so the rest of the directive-resolution pipeline is
+    // unaffected by which wire format carried it in.
+    let record = value
+        .value()
+        .as_record(Some("directive"))
+        .ok_or_else(|| WireError::Preserves("expected <directive ...> record".into()))?;
+    let fields = record.fields();
+
+    let graph = fields
+        .first()
+        .filter(|v| !v.value().is_boolean(false))
+        .map(preserves_to_json)
+        .transpose()?
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(WireError::Json)?;
+
+    let resources = fields
+        .get(1)
+        .filter(|v| !v.value().is_boolean(false))
+        .map(preserves_to_json)
+        .transpose()?
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(WireError::Json)?;
+
+    Ok(UnresolvedDirective { graph, resources })
+}
+
+fn instructions_to_preserves(instructions: &serde_json::Value) -> IOValue {
+    let batch = instructions
+        .as_array()
+        .map(|items| items.iter().map(instruction_to_preserves).collect())
+        .unwrap_or_default();
+
+    Value::from(batch).wrap()
+}
+
+/// Map a single `[opcode, ...]` instruction array, as emitted by
+/// `MainHandle::reconcile`, onto a labelled Preserves record.
+fn instruction_to_preserves(instruction: &serde_json::Value) -> IOValue {
+    let items = instruction.as_array().cloned().unwrap_or_default();
+    let label = match items.first().and_then(|v| v.as_i64()) {
+        Some(0) => "create-node",
+        Some(1) => "remove-node",
+        Some(2) => "append-child",
+        Some(3) => "set-property",
+        Some(4) => "activate-roots",
+        Some(5) => "commit",
+        _ => "instruction",
+    };
+    let mut fields = items
+        .into_iter()
+        .skip(1)
+        .map(|v| preserves_to_value(&v).wrap())
+        .collect::<Vec<_>>();
+    fields.push(Value::Symbol(label.into()).wrap());
+
+    Value::Record(Record(fields)).wrap()
+}
+
+fn preserves_to_value(json: &serde_json::Value) -> Value<IOValue> {
+    match json {
+        serde_json::Value::Null => Value::Boolean(false),
+        serde_json::Value::Bool(b) => Value::Boolean(*b),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::SignedInteger(i.into())
+            } else {
+                Value::Double(n.as_f64().unwrap_or_default())
+            }
+        }
+        serde_json::Value::String(s) => Value::String(s.clone().into()),
+        serde_json::Value::Array(items) => {
+            Value::Sequence(items.iter().map(|v| preserves_to_value(v).wrap()).collect())
+        }
+        serde_json::Value::Object(map) => Value::Dictionary(
+            map.iter()
+                .map(|(k, v)| (Value::String(k.clone().into()).wrap(), preserves_to_value(v).wrap()))
+                .collect(),
+        ),
+    }
+}
+
+/// Convert a decoded Preserves value to its JSON-ish equivalent. Fallible
+/// because a Preserves double can legitimately be `NaN`/`Infinity` (a plain
+/// IEEE-754 bit pattern), which `serde_json` cannot represent -- that's
+/// reported as a `WireError` rather than panicking, same as every other
+/// malformed-input path in this module.
+fn preserves_to_json(value: &IOValue) -> Result<serde_json::Value, WireError> {
+    Ok(match value.value() {
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::SignedInteger(i) => serde_json::json!(i64::try_from(i).unwrap_or_default()),
+        Value::Double(d) => {
+            if !d.is_finite() {
+                return Err(WireError::Preserves(format!(
+                    "double value `{d}` is not representable as JSON"
+                )));
+            }
+            serde_json::json!(d)
+        }
+        Value::String(s) => serde_json::Value::String(s.to_string()),
+        Value::ByteString(bytes) => serde_json::Value::String(base64_encode(bytes)),
+        Value::Sequence(items) => serde_json::Value::Array(
+            items
+                .iter()
+                .map(preserves_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Dictionary(entries) => {
+            let mut map = serde_json::Map::new();
+            for (k, v) in entries.iter() {
+                if let Value::String(key) = k.value() {
+                    map.insert(key.to_string(), preserves_to_json(v)?);
+                }
+            }
+            serde_json::Value::Object(map)
+        }
+        _ => serde_json::Value::Null,
+    })
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_node_instruction_round_trips_as_a_labelled_record() {
+        let instructions = serde_json::json!([[0, 42, "sin"]]);
+        let encoded = encode_instructions(WireFormat::Preserves, &instructions);
+
+        let value = IOValue::from_binary_vec(encoded).expect("valid preserves binary");
+        let batch = value.value().as_sequence().expect("batch is a sequence");
+        let record = batch[0]
+            .value()
+            .as_record(Some("create-node"))
+            .expect("instruction decodes back to a labelled create-node record");
+
+        assert_eq!(record.fields().len(), 2);
+    }
+
+    #[test]
+    fn non_finite_double_is_reported_as_an_error_not_a_panic() {
+        let value = Value::Double(f64::NAN).wrap();
+
+        assert!(preserves_to_json(&value).is_err());
+    }
+}